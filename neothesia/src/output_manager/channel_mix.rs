@@ -0,0 +1,55 @@
+/// Per-channel velocity scaling and General MIDI program override, applied to outgoing
+/// MIDI so a track can be rebalanced and re-voiced without editing the source file.
+pub struct ChannelMix {
+    /// Velocity scale, `0..=127`. `127` leaves velocities untouched.
+    gain: u8,
+    program: Option<u8>,
+    program_sent: bool,
+}
+
+impl Default for ChannelMix {
+    fn default() -> Self {
+        Self {
+            gain: 127,
+            program: None,
+            program_sent: false,
+        }
+    }
+}
+
+impl ChannelMix {
+    pub fn set_gain(&mut self, gain: u8) {
+        self.gain = gain.min(127);
+    }
+
+    pub fn gain(&self) -> u8 {
+        self.gain
+    }
+
+    pub fn set_program(&mut self, program: Option<u8>) {
+        self.program = program;
+        self.program_sent = false;
+    }
+
+    pub fn program(&self) -> Option<u8> {
+        self.program
+    }
+
+    pub fn scale_velocity(&self, vel: u8) -> u8 {
+        (vel as u32 * self.gain as u32 / 127).clamp(1, 127) as u8
+    }
+
+    /// Returns the override program once, the first time it's asked for after being set
+    /// (or after [`Self::clear`]), so it can be sent just before the first note.
+    pub fn take_program_change(&mut self) -> Option<u8> {
+        if self.program_sent {
+            return None;
+        }
+        self.program_sent = true;
+        self.program
+    }
+
+    pub fn clear(&mut self) {
+        self.program_sent = false;
+    }
+}
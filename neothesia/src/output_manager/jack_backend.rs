@@ -0,0 +1,140 @@
+use std::fmt::{self, Display, Formatter};
+
+use jack::{AsyncClient, Client, ClientOptions, MidiOut, Port, PortFlags, ProcessScope};
+use midi_file::midly::{live::LiveEvent, num::u4, MidiMessage};
+use ringbuf::{HeapConsumer, HeapProducer, HeapRb};
+
+use super::{OutputConnection, OutputDescriptor};
+
+/// JACK's own type string for its standard raw MIDI ports, used to filter
+/// `Client::ports` down to destinations we can actually write MIDI into.
+const JACK_MIDI_TYPE: &str = "8 bit raw midi";
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct JackPortInfo {
+    port_name: String,
+}
+
+impl Display for JackPortInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "JACK: {}", self.port_name)
+    }
+}
+
+/// Holds a single JACK client open just to query the graph (list ports), kept alive for
+/// `JackBackend`'s lifetime rather than opened-and-dropped per call. Opening a
+/// connection for real still needs its own client, since activating one for playback
+/// consumes it.
+pub struct JackBackend {
+    client: Client,
+}
+
+impl JackBackend {
+    pub fn new() -> Result<Self, jack::Error> {
+        let (client, _status) = Client::new("Neothesia", ClientOptions::NO_START_SERVER)?;
+        Ok(Self { client })
+    }
+
+    /// Lists the JACK MIDI input ports already on the graph, each a destination we could
+    /// connect our own output port to.
+    pub fn get_outputs(&self) -> Vec<OutputDescriptor> {
+        self.client
+            .ports(None, Some(JACK_MIDI_TYPE), PortFlags::IS_INPUT)
+            .into_iter()
+            .map(|port_name| OutputDescriptor::JackOut(JackPortInfo { port_name }))
+            .collect()
+    }
+
+    pub fn new_output_connection(&self, info: &JackPortInfo) -> Option<JackOutputConnection> {
+        JackOutputConnection::new(&info.port_name).ok()
+    }
+}
+
+/// A single raw MIDI message, at most 3 bytes (covers everything except sysex).
+struct RawEvent {
+    bytes: [u8; 3],
+    len: u8,
+}
+
+struct JackProcessHandler {
+    port: Port<MidiOut>,
+    consumer: HeapConsumer<RawEvent>,
+}
+
+impl jack::ProcessHandler for JackProcessHandler {
+    fn process(&mut self, _client: &Client, ps: &ProcessScope) -> jack::Control {
+        let mut writer = self.port.writer(ps);
+        while let Some(event) = self.consumer.pop() {
+            let _ = writer.write(&jack::RawMidi {
+                time: 0,
+                bytes: &event.bytes[..event.len as usize],
+            });
+        }
+
+        jack::Control::Continue
+    }
+}
+
+pub struct JackOutputConnection {
+    producer: HeapProducer<RawEvent>,
+    // Kept alive for as long as the connection is open; dropping it deactivates the client.
+    _async_client: AsyncClient<(), JackProcessHandler>,
+}
+
+impl JackOutputConnection {
+    fn new(target_port_name: &str) -> Result<Self, jack::Error> {
+        let (client, _status) = Client::new("Neothesia", ClientOptions::NO_START_SERVER)?;
+        let port = client.register_port("midi_out", MidiOut)?;
+        let our_port_name = port.name()?;
+
+        let rb = HeapRb::new(1024);
+        let (producer, consumer) = rb.split();
+
+        let handler = JackProcessHandler { port, consumer };
+        let async_client = client.activate_async((), handler)?;
+        async_client
+            .as_client()
+            .connect_ports_by_name(&our_port_name, target_port_name)?;
+
+        Ok(Self {
+            producer,
+            _async_client: async_client,
+        })
+    }
+
+    fn push_raw(&mut self, bytes: &[u8]) {
+        let mut buf = [0u8; 3];
+        let len = bytes.len().min(3);
+        buf[..len].copy_from_slice(&bytes[..len]);
+        let _ = self.producer.push(RawEvent {
+            bytes: buf,
+            len: len as u8,
+        });
+    }
+}
+
+impl OutputConnection for JackOutputConnection {
+    fn midi_event(&mut self, channel: u4, msg: MidiMessage) {
+        let event = LiveEvent::Midi {
+            channel,
+            message: msg,
+        };
+
+        let mut buf = Vec::with_capacity(3);
+        if event.write(&mut buf).is_ok() {
+            self.push_raw(&buf);
+        }
+    }
+
+    fn stop_all(&mut self) {
+        for channel in 0..16 {
+            self.midi_event(
+                u4::new(channel),
+                MidiMessage::Controller {
+                    controller: 123.into(),
+                    value: 0.into(),
+                },
+            );
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use std::fmt::{self, Display, Formatter};
+
+use midi_file::midly::{live::LiveEvent, num::u4, MidiMessage};
+use midir::{MidiInput, MidiInputConnection, MidiOutput, MidiOutputConnection};
+
+use super::{OutputConnection, OutputDescriptor};
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct MidiPortInfo {
+    port_name: String,
+}
+
+impl Display for MidiPortInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.port_name)
+    }
+}
+
+pub struct MidiBackend {
+    output: MidiOutput,
+    input: Option<MidiInput>,
+}
+
+impl MidiBackend {
+    pub fn new() -> Result<Self, midir::InitError> {
+        let output = MidiOutput::new("Neothesia")?;
+
+        let input = match MidiInput::new("Neothesia") {
+            Ok(input) => Some(input),
+            Err(err) => {
+                log::error!("{}", err);
+                None
+            }
+        };
+
+        Ok(Self { output, input })
+    }
+
+    pub fn get_outputs(&self) -> Vec<OutputDescriptor> {
+        self.output
+            .ports()
+            .iter()
+            .filter_map(|port| {
+                self.output.port_name(port).ok().map(|port_name| {
+                    OutputDescriptor::MidiOut(MidiPortInfo { port_name })
+                })
+            })
+            .collect()
+    }
+
+    pub fn new_output_connection(info: &MidiPortInfo) -> Option<MidiOutputConnection> {
+        let output = MidiOutput::new("Neothesia").ok()?;
+        let port = output
+            .ports()
+            .into_iter()
+            .find(|port| output.port_name(port).as_deref() == Ok(info.port_name.as_str()))?;
+
+        output
+            .connect(&port, "neothesia-out")
+            .ok()
+            .map(|conn| MidiOutputConnection { conn })
+    }
+
+    /// Ports that can be opened for live input, e.g. a physical keyboard for Play-Along.
+    pub fn get_inputs(&self) -> Vec<MidiPortInfo> {
+        let Some(input) = &self.input else {
+            return Vec::new();
+        };
+
+        input
+            .ports()
+            .iter()
+            .filter_map(|port| {
+                input
+                    .port_name(port)
+                    .ok()
+                    .map(|port_name| MidiPortInfo { port_name })
+            })
+            .collect()
+    }
+
+    pub fn new_input_connection(
+        info: &MidiPortInfo,
+        mut on_event: impl FnMut(u4, MidiMessage) + Send + 'static,
+    ) -> Option<MidiInputConnection<()>> {
+        let input = MidiInput::new("Neothesia").ok()?;
+        let port = input
+            .ports()
+            .into_iter()
+            .find(|port| input.port_name(port).as_deref() == Ok(info.port_name.as_str()))?;
+
+        input
+            .connect(
+                &port,
+                "neothesia-in",
+                move |_stamp, bytes, _| {
+                    if let Ok(LiveEvent::Midi { channel, message }) = LiveEvent::parse(bytes) {
+                        on_event(channel, message);
+                    }
+                },
+                (),
+            )
+            .ok()
+    }
+}
+
+pub struct MidiOutputConnection {
+    conn: midir::MidiOutputConnection,
+}
+
+impl OutputConnection for MidiOutputConnection {
+    fn midi_event(&mut self, channel: u4, msg: MidiMessage) {
+        let event = LiveEvent::Midi {
+            channel,
+            message: msg,
+        };
+
+        let mut buf = Vec::with_capacity(3);
+        if event.write(&mut buf).is_ok() {
+            let _ = self.conn.send(&buf);
+        }
+    }
+
+    fn stop_all(&mut self) {
+        for channel in 0..16 {
+            self.midi_event(
+                u4::new(channel),
+                MidiMessage::Controller {
+                    controller: 123.into(),
+                    value: 0.into(),
+                },
+            );
+        }
+    }
+}
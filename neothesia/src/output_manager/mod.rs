@@ -1,5 +1,17 @@
 mod midi_backend;
-use midi_backend::{MidiBackend, MidiPortInfo};
+pub use midi_backend::MidiPortInfo;
+use midi_backend::MidiBackend;
+use midir::MidiInputConnection;
+
+mod transpose;
+pub use transpose::ScaleConfig;
+use transpose::ChannelTranspose;
+
+mod channel_mix;
+use channel_mix::ChannelMix;
+
+mod recorder;
+use recorder::RecorderConnection;
 
 #[cfg(feature = "synth")]
 mod synth_backend;
@@ -7,6 +19,12 @@ mod synth_backend;
 #[cfg(feature = "synth")]
 use synth_backend::SynthBackend;
 
+#[cfg(all(feature = "jack", target_os = "linux"))]
+mod jack_backend;
+
+#[cfg(all(feature = "jack", target_os = "linux"))]
+use jack_backend::{JackBackend, JackPortInfo};
+
 use std::{
     fmt::{self, Display, Formatter},
     path::PathBuf,
@@ -19,6 +37,11 @@ pub enum OutputDescriptor {
     #[cfg(feature = "synth")]
     Synth(Option<PathBuf>),
     MidiOut(MidiPortInfo),
+    #[cfg(all(feature = "jack", target_os = "linux"))]
+    JackOut(JackPortInfo),
+    /// Tees the performance to a standard MIDI file at this path, written out when the
+    /// connection's `stop_all` runs (i.e. when playback stops).
+    FileRecorder(PathBuf),
     DummyOutput,
 }
 
@@ -28,6 +51,9 @@ impl Display for OutputDescriptor {
             #[cfg(feature = "synth")]
             OutputDescriptor::Synth(_) => write!(f, "Buildin Synth"),
             OutputDescriptor::MidiOut(info) => write!(f, "{}", info),
+            #[cfg(all(feature = "jack", target_os = "linux"))]
+            OutputDescriptor::JackOut(info) => write!(f, "{}", info),
+            OutputDescriptor::FileRecorder(path) => write!(f, "Record: {}", path.display()),
             OutputDescriptor::DummyOutput => write!(f, "No Output"),
         }
     }
@@ -38,6 +64,14 @@ pub trait OutputConnection {
     fn stop_all(&mut self);
 }
 
+/// A MIDI message received from a live input device, forwarded to whoever polls
+/// [`OutputManager::poll_input_events`] (e.g. the Play-Along note-judging logic).
+#[derive(Debug, Clone)]
+pub struct InputEvent {
+    pub channel: u4,
+    pub message: MidiMessage,
+}
+
 struct DummyOutput {}
 impl OutputConnection for DummyOutput {
     fn midi_event(&mut self, _channel: u4, _msg: MidiMessage) {}
@@ -48,8 +82,31 @@ pub struct OutputManager {
     #[cfg(feature = "synth")]
     synth_backend: Option<SynthBackend>,
     midi_backend: Option<MidiBackend>,
+    #[cfg(all(feature = "jack", target_os = "linux"))]
+    jack_backend: Option<JackBackend>,
+
+    /// Every output currently opened, e.g. the built-in synth plus a couple of MIDI-out
+    /// ports, each reachable by the index channels are routed to. A freed slot becomes
+    /// `None` rather than being removed, so indices already held by `channel_routes`,
+    /// `default_connection` or `recorder_index` stay valid.
+    connections: Vec<Option<(OutputDescriptor, Box<dyn OutputConnection>)>>,
+    /// Connection used by channels with no explicit route.
+    default_connection: Option<usize>,
+    /// Per-channel override into `connections`, for split setups (e.g. melody to a
+    /// hardware synth, accompaniment to the internal SoundFont).
+    channel_routes: [Option<usize>; 16],
 
-    output_connection: (OutputDescriptor, Box<dyn OutputConnection>),
+    input_connection: Option<MidiInputConnection<()>>,
+    input_events: std::sync::mpsc::Receiver<InputEvent>,
+    input_events_tx: std::sync::mpsc::Sender<InputEvent>,
+    echo_input: bool,
+
+    channel_transpose: [ChannelTranspose; 16],
+    channel_mix: [ChannelMix; 16],
+
+    /// Connection tee'd alongside whatever each channel is routed to, so a Play-Along
+    /// session can be saved without disturbing the normal output routing.
+    recorder_index: Option<usize>,
 }
 
 impl Default for OutputManager {
@@ -77,12 +134,37 @@ impl OutputManager {
             }
         };
 
+        #[cfg(all(feature = "jack", target_os = "linux"))]
+        let jack_backend = match JackBackend::new() {
+            Ok(jack_backend) => Some(jack_backend),
+            Err(err) => {
+                log::error!("{:?}", err);
+                None
+            }
+        };
+
+        let (input_events_tx, input_events) = std::sync::mpsc::channel();
+
         Self {
             #[cfg(feature = "synth")]
             synth_backend,
             midi_backend,
+            #[cfg(all(feature = "jack", target_os = "linux"))]
+            jack_backend,
+
+            connections: Vec::new(),
+            default_connection: None,
+            channel_routes: [None; 16],
+
+            input_connection: None,
+            input_events,
+            input_events_tx,
+            echo_input: false,
 
-            output_connection: (OutputDescriptor::DummyOutput, Box::new(DummyOutput {})),
+            channel_transpose: Default::default(),
+            channel_mix: Default::default(),
+
+            recorder_index: None,
         }
     }
 
@@ -97,45 +179,335 @@ impl OutputManager {
             outs.append(&mut midi.get_outputs());
         }
 
+        #[cfg(all(feature = "jack", target_os = "linux"))]
+        if let Some(jack) = &self.jack_backend {
+            outs.append(&mut jack.get_outputs());
+        }
+
         outs.push(OutputDescriptor::DummyOutput);
 
         outs
     }
 
-    pub fn connect(&mut self, desc: OutputDescriptor) {
-        if desc != self.output_connection.0 {
-            match desc {
-                #[cfg(feature = "synth")]
-                OutputDescriptor::Synth(ref font) => {
-                    if let Some(ref mut synth) = self.synth_backend {
-                        if let Some(font) = font.clone() {
-                            self.output_connection =
-                                (desc, Box::new(synth.new_output_connection(&font)));
-                        } else if let Some(path) = crate::utils::resources::default_sf2() {
-                            if path.exists() {
-                                self.output_connection =
-                                    (desc, Box::new(synth.new_output_connection(&path)));
-                            }
-                        }
-                    }
-                }
-                OutputDescriptor::MidiOut(ref info) => {
-                    if let Some(conn) = MidiBackend::new_output_connection(info) {
-                        self.output_connection = (desc, Box::new(conn));
-                    }
+    pub fn inputs(&self) -> Vec<MidiPortInfo> {
+        self.midi_backend
+            .as_ref()
+            .map(|midi| midi.get_inputs())
+            .unwrap_or_default()
+    }
+
+    /// Opens a live input device for Play-Along. When `echo` is set, incoming notes are
+    /// also sent straight to the current output so the player hears themselves.
+    pub fn connect_input(&mut self, info: &MidiPortInfo, echo: bool) {
+        let tx = self.input_events_tx.clone();
+        self.input_connection =
+            MidiBackend::new_input_connection(info, move |channel, message| {
+                let _ = tx.send(InputEvent { channel, message });
+            });
+        self.echo_input = echo;
+    }
+
+    pub fn disconnect_input(&mut self) {
+        self.input_connection = None;
+    }
+
+    /// Whether incoming notes are currently echoed to the active output, so the UI can
+    /// read back the choice last passed to [`Self::connect_input`] and let it be toggled
+    /// independently of picking a device.
+    pub fn echo_input(&self) -> bool {
+        self.echo_input
+    }
+
+    pub fn set_echo_input(&mut self, echo: bool) {
+        self.echo_input = echo;
+    }
+
+    /// Drains events received from the connected input device since the last poll.
+    pub fn poll_input_events(&mut self) -> Vec<InputEvent> {
+        let events: Vec<InputEvent> = self.input_events.try_iter().collect();
+
+        if self.echo_input {
+            for event in &events {
+                // Routed through midi_event (not connection_for directly) so a live
+                // Play-Along performance is teed to the recorder like any other output.
+                self.midi_event(event.channel, event.message);
+            }
+        }
+
+        events
+    }
+
+    pub fn set_channel_transpose(&mut self, channel: u4, semitones: i8) {
+        self.channel_transpose[channel.as_int() as usize].set_transpose(semitones);
+    }
+
+    pub fn channel_transpose(&self, channel: u4) -> i8 {
+        self.channel_transpose[channel.as_int() as usize].transpose()
+    }
+
+    pub fn set_channel_scale(&mut self, channel: u4, scale: Option<ScaleConfig>) {
+        self.channel_transpose[channel.as_int() as usize].set_scale(scale);
+    }
+
+    pub fn channel_scale(&self, channel: u4) -> Option<ScaleConfig> {
+        self.channel_transpose[channel.as_int() as usize].scale()
+    }
+
+    /// Starts teeing every outgoing MIDI event to a recording, saved to `path` as a
+    /// standard MIDI file once [`Self::stop_recording`] (or [`Self::stop_all`]) runs.
+    ///
+    /// Unlike [`Self::connection_index`], this never reuses an existing connection: two
+    /// takes to the same path must not share a `RecorderConnection`, or the second take
+    /// would resume the first one's clock and append to its buffered events instead of
+    /// starting a fresh recording.
+    pub fn start_recording(&mut self, path: PathBuf) {
+        if self.recorder_index.is_some() {
+            self.stop_recording();
+        }
+
+        let desc = OutputDescriptor::FileRecorder(path);
+        if let Some(conn) = self.open_connection(&desc) {
+            self.recorder_index = Some(self.store_connection(desc, conn));
+        }
+    }
+
+    /// Flushes the current take to disk and frees its connection, so a later take (or a
+    /// stray global [`Self::stop_all`]) can't resume or rewrite it.
+    pub fn stop_recording(&mut self) {
+        if self.recorder_index.take().is_none() {
+            return;
+        }
+
+        // The recorder is no longer referenced by anything now, so prune_connections
+        // flushes it with a last stop_all and frees its slot.
+        self.prune_connections();
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recorder_index.is_some()
+    }
+
+    pub fn set_channel_gain(&mut self, channel: u4, gain: u8) {
+        self.channel_mix[channel.as_int() as usize].set_gain(gain);
+    }
+
+    pub fn channel_gain(&self, channel: u4) -> u8 {
+        self.channel_mix[channel.as_int() as usize].gain()
+    }
+
+    pub fn set_channel_program(&mut self, channel: u4, program: Option<u8>) {
+        self.channel_mix[channel.as_int() as usize].set_program(program);
+    }
+
+    pub fn channel_program(&self, channel: u4) -> Option<u8> {
+        self.channel_mix[channel.as_int() as usize].program()
+    }
+
+    fn open_connection(&mut self, desc: &OutputDescriptor) -> Option<Box<dyn OutputConnection>> {
+        match desc {
+            #[cfg(feature = "synth")]
+            OutputDescriptor::Synth(font) => {
+                let synth = self.synth_backend.as_mut()?;
+                if let Some(font) = font.clone() {
+                    Some(Box::new(synth.new_output_connection(&font)))
+                } else {
+                    let path = crate::utils::resources::default_sf2()?;
+                    path.exists().then(|| {
+                        Box::new(synth.new_output_connection(&path)) as Box<dyn OutputConnection>
+                    })
                 }
-                OutputDescriptor::DummyOutput => {
-                    self.output_connection = (desc, Box::new(DummyOutput {}));
+            }
+            OutputDescriptor::MidiOut(info) => MidiBackend::new_output_connection(info)
+                .map(|conn| Box::new(conn) as Box<dyn OutputConnection>),
+            #[cfg(all(feature = "jack", target_os = "linux"))]
+            OutputDescriptor::JackOut(info) => self
+                .jack_backend
+                .as_ref()?
+                .new_output_connection(info)
+                .map(|conn| Box::new(conn) as Box<dyn OutputConnection>),
+            OutputDescriptor::FileRecorder(path) => {
+                Some(Box::new(RecorderConnection::new(path.clone())))
+            }
+            OutputDescriptor::DummyOutput => Some(Box::new(DummyOutput {})),
+        }
+    }
+
+    /// Finds the already-open connection matching `desc`, opening a new one if needed,
+    /// reusing a freed slot over growing the pool where one is available.
+    fn connection_index(&mut self, desc: OutputDescriptor) -> Option<usize> {
+        if let Some(index) = self
+            .connections
+            .iter()
+            .position(|slot| matches!(slot, Some((d, _)) if *d == desc))
+        {
+            return Some(index);
+        }
+
+        let conn = self.open_connection(&desc)?;
+        Some(self.store_connection(desc, conn))
+    }
+
+    /// Inserts a connection into a freed slot if one exists, otherwise grows the pool.
+    fn store_connection(&mut self, desc: OutputDescriptor, conn: Box<dyn OutputConnection>) -> usize {
+        if let Some(index) = self.connections.iter().position(|slot| slot.is_none()) {
+            self.connections[index] = Some((desc, conn));
+            index
+        } else {
+            self.connections.push(Some((desc, conn)));
+            self.connections.len() - 1
+        }
+    }
+
+    /// Whether `index` is still referenced by a channel route, the default output or the
+    /// recorder tee.
+    fn is_referenced(&self, index: usize) -> bool {
+        self.default_connection == Some(index)
+            || self.recorder_index == Some(index)
+            || self.channel_routes.iter().any(|route| *route == Some(index))
+    }
+
+    /// Frees every connection no longer referenced by any route, the default output or
+    /// the recorder, so re-routing a channel or changing the default doesn't leak the
+    /// connection it replaced. Each freed connection gets a last `stop_all` first, since
+    /// it's about to become unreachable from [`Self::stop_all`].
+    fn prune_connections(&mut self) {
+        for index in 0..self.connections.len() {
+            if !self.is_referenced(index) {
+                if let Some((_, mut conn)) = self.connections[index].take() {
+                    conn.stop_all();
                 }
             }
         }
     }
 
+    /// Sets the output used by channels with no explicit [`Self::connect_channel`] route.
+    pub fn set_default(&mut self, desc: OutputDescriptor) {
+        self.default_connection = self.connection_index(desc);
+        self.prune_connections();
+    }
+
+    /// Routes a single channel to `desc`, independently of the default output. This is
+    /// how split setups are built, e.g. melody to a hardware synth and accompaniment to
+    /// the internal SoundFont.
+    pub fn connect_channel(&mut self, channel: u4, desc: OutputDescriptor) {
+        self.channel_routes[channel.as_int() as usize] = self.connection_index(desc);
+        self.prune_connections();
+    }
+
+    pub fn disconnect_channel(&mut self, channel: u4) {
+        self.channel_routes[channel.as_int() as usize] = None;
+        self.prune_connections();
+    }
+
+    pub fn channel_output(&self, channel: u4) -> Option<OutputDescriptor> {
+        let index = self.channel_routes[channel.as_int() as usize]?;
+        self.connections
+            .get(index)
+            .and_then(|slot| slot.as_ref())
+            .map(|(desc, _)| desc.clone())
+    }
+
+    fn connection_for(&mut self, channel: u4) -> Option<&mut Box<dyn OutputConnection>> {
+        let index = self.channel_routes[channel.as_int() as usize].or(self.default_connection)?;
+        self.connections
+            .get_mut(index)
+            .and_then(|slot| slot.as_mut())
+            .map(|(_, conn)| conn)
+    }
+
     pub fn midi_event(&mut self, channel: u4, msg: MidiMessage) {
-        self.output_connection.1.midi_event(channel, msg);
+        let msg = self.apply_transpose(channel, msg);
+        let Some(msg) = self.apply_mix(channel, msg) else {
+            return;
+        };
+
+        if let Some(program) = self.pending_program_change(channel, &msg) {
+            self.dispatch(
+                channel,
+                MidiMessage::ProgramChange {
+                    program: program.into(),
+                },
+            );
+        }
+
+        self.dispatch(channel, msg);
+    }
+
+    /// Sends a message to the channel's routed (or default) output, tee'd to the
+    /// recorder if one is active, same as [`Self::midi_event`]'s own note traffic.
+    fn dispatch(&mut self, channel: u4, msg: MidiMessage) {
+        if let Some(conn) = self.connection_for(channel) {
+            conn.midi_event(channel, msg);
+        }
+
+        if let Some(index) = self.recorder_index {
+            if let Some((_, conn)) = self.connections.get_mut(index).and_then(|slot| slot.as_mut()) {
+                conn.midi_event(channel, msg);
+            }
+        }
+    }
+
+    /// Scales Note On velocity by the channel's gain and drops the file's own program
+    /// changes on channels with an override, so [`Self::pending_program_change`] is the
+    /// only thing that emits `ProgramChange` on them.
+    fn apply_mix(&mut self, channel: u4, msg: MidiMessage) -> Option<MidiMessage> {
+        let mix = &self.channel_mix[channel.as_int() as usize];
+        match msg {
+            MidiMessage::ProgramChange { .. } if mix.program().is_some() => None,
+            MidiMessage::NoteOn { key, vel } if vel.as_int() > 0 => Some(MidiMessage::NoteOn {
+                key,
+                vel: mix.scale_velocity(vel.as_int()).into(),
+            }),
+            other => Some(other),
+        }
+    }
+
+    /// Returns the channel's override program the first time a Note On passes through
+    /// after it was set, so it can be sent just before that note.
+    fn pending_program_change(&mut self, channel: u4, msg: &MidiMessage) -> Option<u8> {
+        if !matches!(msg, MidiMessage::NoteOn { vel, .. } if vel.as_int() > 0) {
+            return None;
+        }
+        self.channel_mix[channel.as_int() as usize].take_program_change()
+    }
+
+    fn apply_transpose(&mut self, channel: u4, msg: MidiMessage) -> MidiMessage {
+        let transpose = &mut self.channel_transpose[channel.as_int() as usize];
+        match msg {
+            // A Note On with velocity 0 is running-status shorthand for Note Off, used
+            // by most MIDI files. It must resolve through the same note_off lookup so it
+            // lands on the key the original Note On was remapped to, not a fresh remap.
+            MidiMessage::NoteOn { key, vel } if vel.as_int() == 0 => MidiMessage::NoteOn {
+                key: transpose.note_off(key.as_int()).into(),
+                vel,
+            },
+            MidiMessage::NoteOn { key, vel } => MidiMessage::NoteOn {
+                key: transpose.note_on(key.as_int()).into(),
+                vel,
+            },
+            MidiMessage::NoteOff { key, vel } => MidiMessage::NoteOff {
+                key: transpose.note_off(key.as_int()).into(),
+                vel,
+            },
+            MidiMessage::Aftertouch { key, vel } => MidiMessage::Aftertouch {
+                key: transpose.aftertouch(key.as_int()).into(),
+                vel,
+            },
+            other => other,
+        }
     }
 
     pub fn stop_all(&mut self) {
-        self.output_connection.1.stop_all();
+        for transpose in &mut self.channel_transpose {
+            transpose.clear();
+        }
+        for mix in &mut self.channel_mix {
+            mix.clear();
+        }
+        for slot in &mut self.connections {
+            if let Some((_, conn)) = slot {
+                conn.stop_all();
+            }
+        }
     }
 }
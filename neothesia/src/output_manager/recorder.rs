@@ -0,0 +1,84 @@
+use std::{path::PathBuf, time::Instant};
+
+use midi_file::midly::{
+    self,
+    num::{u15, u24, u28, u4},
+    MetaMessage, MidiMessage, Smf, TrackEvent, TrackEventKind,
+};
+
+use super::OutputConnection;
+
+/// Ticks per quarter note, paired with a fixed tempo below so one tick equals one
+/// millisecond, matching the timestamps events are pushed with.
+const TICKS_PER_QUARTER: u16 = 1000;
+const MICROS_PER_QUARTER: u32 = 1_000_000;
+
+/// Tees alongside the active audible output, capturing everything sent through it with
+/// real-time timestamps and writing it out as a standard MIDI file once the take ends.
+pub struct RecorderConnection {
+    path: PathBuf,
+    started_at: Instant,
+    last_event_ms: u32,
+    events: Vec<(u32, u4, MidiMessage)>,
+}
+
+impl RecorderConnection {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            started_at: Instant::now(),
+            last_event_ms: 0,
+            events: Vec::new(),
+        }
+    }
+
+    fn write_file(&self) -> std::io::Result<()> {
+        let mut track = Vec::new();
+
+        track.push(TrackEvent {
+            delta: 0.into(),
+            kind: TrackEventKind::Meta(MetaMessage::Tempo(u24::new(MICROS_PER_QUARTER))),
+        });
+
+        for (delta, channel, message) in &self.events {
+            track.push(TrackEvent {
+                delta: u28::new(*delta),
+                kind: TrackEventKind::Midi {
+                    channel: *channel,
+                    message: *message,
+                },
+            });
+        }
+
+        track.push(TrackEvent {
+            delta: 0.into(),
+            kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+        });
+
+        let smf = Smf {
+            header: midly::Header::new(
+                midly::Format::SingleTrack,
+                midly::Timing::Metrical(u15::new(TICKS_PER_QUARTER)),
+            ),
+            tracks: vec![track],
+        };
+
+        smf.save(&self.path)
+    }
+}
+
+impl OutputConnection for RecorderConnection {
+    fn midi_event(&mut self, channel: u4, msg: MidiMessage) {
+        let now_ms = self.started_at.elapsed().as_millis() as u32;
+        let delta = now_ms.saturating_sub(self.last_event_ms);
+        self.last_event_ms = now_ms;
+        self.events.push((delta, channel, msg));
+    }
+
+    /// Flushes the take captured so far to `path` as a standard MIDI file.
+    fn stop_all(&mut self) {
+        if let Err(err) = self.write_file() {
+            log::error!("{}", err);
+        }
+    }
+}
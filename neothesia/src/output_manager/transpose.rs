@@ -0,0 +1,97 @@
+/// A key + scale to snap incoming notes into, e.g. to retune an imported MIDI file
+/// into a chosen key without editing the file itself.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct ScaleConfig {
+    /// Root pitch class, `0..=11` (0 = C).
+    pub root: u8,
+    /// 12-bit mask of allowed pitch classes relative to `root`, e.g. major is
+    /// `0b101010110101`.
+    pub mask: u16,
+}
+
+impl ScaleConfig {
+    pub const MAJOR_MASK: u16 = 0b101010110101;
+
+    /// Moves `note` to the nearest pitch class allowed by this scale, preferring to
+    /// move downward when a note is equidistant from two allowed pitch classes.
+    pub fn snap(&self, note: u8) -> u8 {
+        let pitch_class = (note as i16 - self.root as i16).rem_euclid(12);
+        if self.allows(pitch_class) {
+            return note;
+        }
+
+        for distance in 1..=6 {
+            if self.allows(pitch_class - distance) {
+                return (note as i16 - distance).clamp(0, 127) as u8;
+            }
+            if self.allows(pitch_class + distance) {
+                return (note as i16 + distance).clamp(0, 127) as u8;
+            }
+        }
+
+        note
+    }
+
+    fn allows(&self, pitch_class: i16) -> bool {
+        let pitch_class = pitch_class.rem_euclid(12) as u8;
+        (self.mask >> pitch_class) & 1 == 1
+    }
+}
+
+/// Per-channel transpose + scale quantization, applied to outgoing MIDI before it
+/// reaches an [`super::OutputConnection`]. Tracks the note number each incoming Note On
+/// was remapped to so the matching Note Off (and aftertouch) can be translated
+/// identically.
+#[derive(Default)]
+pub struct ChannelTranspose {
+    transpose: i8,
+    scale: Option<ScaleConfig>,
+    note_map: std::collections::HashMap<u8, u8>,
+}
+
+impl ChannelTranspose {
+    pub fn set_transpose(&mut self, semitones: i8) {
+        self.transpose = semitones;
+    }
+
+    pub fn transpose(&self) -> i8 {
+        self.transpose
+    }
+
+    pub fn set_scale(&mut self, scale: Option<ScaleConfig>) {
+        self.scale = scale;
+    }
+
+    pub fn scale(&self) -> Option<ScaleConfig> {
+        self.scale
+    }
+
+    pub fn clear(&mut self) {
+        self.note_map.clear();
+    }
+
+    fn remap(&self, note: u8) -> u8 {
+        let transposed = (note as i16 + self.transpose as i16).clamp(0, 127) as u8;
+        match self.scale {
+            Some(scale) => scale.snap(transposed),
+            None => transposed,
+        }
+    }
+
+    /// Remaps a Note On and remembers the mapping for the matching Note Off/aftertouch.
+    pub fn note_on(&mut self, note: u8) -> u8 {
+        let mapped = self.remap(note);
+        self.note_map.insert(note, mapped);
+        mapped
+    }
+
+    /// Looks up the note a previous Note On was remapped to, removing the entry.
+    pub fn note_off(&mut self, note: u8) -> u8 {
+        self.note_map.remove(&note).unwrap_or(note)
+    }
+
+    /// Looks up the note a previous Note On was remapped to, without removing it.
+    pub fn aftertouch(&self, note: u8) -> u8 {
+        self.note_map.get(&note).copied().unwrap_or(note)
+    }
+}
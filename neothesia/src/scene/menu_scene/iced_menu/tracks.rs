@@ -3,13 +3,35 @@ use iced_core::{
     Alignment, Length, Padding,
 };
 use iced_style::Theme;
-use iced_widget::{button, column as col, container, row, vertical_space, Component};
+use iced_widget::{button, column as col, container, pick_list, row, slider, vertical_space, Component};
 
-use crate::{context::Context, scene::menu_scene::icons, song::PlayerConfig};
+use midi_file::midly::num::u4;
+
+use crate::{
+    context::Context,
+    output_manager::{MidiPortInfo, OutputDescriptor, ScaleConfig},
+    scene::menu_scene::icons,
+    song::PlayerConfig,
+};
 use neothesia_iced_widgets::{BarLayout, Element, Layout, NeoBtn, Renderer};
 
 use super::{centered_text, theme};
 
+const NOTE_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+/// Channels are a property of a track's own notes, not of its position in the file —
+/// two tracks can share a channel, and a track's channel rarely matches its index. All
+/// per-track transpose/scale/output/mix controls key off this rather than `track_id`.
+fn track_channel(track: &midi_file::Track) -> u4 {
+    track
+        .notes
+        .first()
+        .map(|note| u4::new(note.channel))
+        .unwrap_or_else(|| u4::new(0))
+}
+
 pub struct TracksPage<'a, MSG> {
     ctx: &'a mut Context,
     on_back: Option<Box<dyn Fn() -> MSG>>,
@@ -34,6 +56,16 @@ impl<'a, MSG> TracksPage<'a, MSG> {
         self.on_play = Some(Box::new(cb));
         self
     }
+
+    /// Looks up a track's channel by id, for `update()` handlers that only carry the id.
+    fn track_channel(&self, track_id: usize) -> u4 {
+        self.ctx
+            .song
+            .as_ref()
+            .and_then(|song| song.file.tracks.get(track_id))
+            .map(track_channel)
+            .unwrap_or_else(|| u4::new(0))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -43,6 +75,14 @@ pub enum Event {
     AllTracksPlayer(PlayerConfig),
     TrackPlayer(usize, PlayerConfig),
     TrackVisibility(usize, bool),
+    SelectInput(MidiPortInfo),
+    ToggleInputEcho(bool),
+    TrackTranspose(usize, i8),
+    TrackScaleToggle(usize),
+    TrackScaleRoot(usize, u8),
+    TrackOutput(usize, OutputDescriptor),
+    TrackGain(usize, u8),
+    TrackProgram(usize, Option<&'static str>),
 }
 
 impl<'a, MSG> Component<MSG, Theme, Renderer> for TracksPage<'a, MSG> {
@@ -68,6 +108,54 @@ impl<'a, MSG> Component<MSG, Theme, Renderer> for TracksPage<'a, MSG> {
                     song.config.tracks[track].visible = visible;
                 }
             }
+            Event::SelectInput(info) => {
+                let echo = self.ctx.output_manager.echo_input();
+                self.ctx.output_manager.connect_input(&info, echo);
+            }
+            Event::ToggleInputEcho(echo) => {
+                self.ctx.output_manager.set_echo_input(echo);
+            }
+            Event::TrackTranspose(track, semitones) => {
+                let channel = self.track_channel(track);
+                self.ctx
+                    .output_manager
+                    .set_channel_transpose(channel, semitones);
+            }
+            Event::TrackScaleToggle(track) => {
+                let channel = self.track_channel(track);
+                let scale = match self.ctx.output_manager.channel_scale(channel) {
+                    Some(_) => None,
+                    None => Some(ScaleConfig {
+                        root: 0,
+                        mask: ScaleConfig::MAJOR_MASK,
+                    }),
+                };
+                self.ctx.output_manager.set_channel_scale(channel, scale);
+            }
+            Event::TrackScaleRoot(track, root) => {
+                let channel = self.track_channel(track);
+                if let Some(mut scale) = self.ctx.output_manager.channel_scale(channel) {
+                    scale.root = root % 12;
+                    self.ctx.output_manager.set_channel_scale(channel, Some(scale));
+                }
+            }
+            Event::TrackOutput(track, desc) => {
+                let channel = self.track_channel(track);
+                self.ctx.output_manager.connect_channel(channel, desc);
+            }
+            Event::TrackGain(track, gain) => {
+                let channel = self.track_channel(track);
+                self.ctx.output_manager.set_channel_gain(channel, gain);
+            }
+            Event::TrackProgram(track, name) => {
+                let program = name
+                    .and_then(|name| midi_file::INSTRUMENT_NAMES.iter().position(|n| *n == name))
+                    .map(|program| program as u8);
+                let channel = self.track_channel(track);
+                self.ctx
+                    .output_manager
+                    .set_channel_program(channel, program);
+            }
             Event::Back => return self.on_back.as_ref().map(|cb| cb()),
             Event::Play => return self.on_play.as_ref().map(|cb| cb()),
         }
@@ -125,6 +213,84 @@ impl<'a, MSG> Component<MSG, Theme, Renderer> for TracksPage<'a, MSG> {
                     .active(active)
                     .active_color(color);
 
+                let channel = track_channel(track);
+                let transpose = ctx.output_manager.channel_transpose(channel);
+                let scale = ctx.output_manager.channel_scale(channel);
+
+                let transpose_row = row![
+                    button(centered_text("-"))
+                        .on_press(Event::TrackTranspose(track.track_id, transpose - 1))
+                        .style(theme::button()),
+                    centered_text(format!("{transpose:+}")),
+                    button(centered_text("+"))
+                        .on_press(Event::TrackTranspose(track.track_id, transpose + 1))
+                        .style(theme::button()),
+                    button(centered_text(if scale.is_some() { "Snap: On" } else { "Snap: Off" }))
+                        .on_press(Event::TrackScaleToggle(track.track_id))
+                        .style(theme::button()),
+                ]
+                .spacing(6)
+                .align_items(Alignment::Center);
+
+                let transpose_row = if let Some(scale) = scale {
+                    transpose_row.push(
+                        button(centered_text(NOTE_NAMES[scale.root as usize]))
+                            .on_press(Event::TrackScaleRoot(track.track_id, (scale.root + 1) % 12))
+                            .style(theme::button()),
+                    )
+                } else {
+                    transpose_row
+                };
+
+                let outputs = ctx.output_manager.outputs();
+                let current_output = ctx.output_manager.channel_output(channel);
+                let next_output = {
+                    let current_index = current_output
+                        .as_ref()
+                        .and_then(|desc| outputs.iter().position(|o| o == desc));
+                    let next_index = current_index.map_or(0, |i| (i + 1) % outputs.len());
+                    outputs.get(next_index).cloned()
+                };
+
+                let destination_row = row![button(centered_text(format!(
+                    "Output: {}",
+                    current_output
+                        .map(|d| d.to_string())
+                        .unwrap_or_else(|| "Default".into())
+                )))
+                .on_press_maybe(
+                    next_output.map(|desc| Event::TrackOutput(track.track_id, desc))
+                )
+                .style(theme::button())]
+                .spacing(6);
+
+                let gain = ctx.output_manager.channel_gain(channel);
+                let gain_row = row![
+                    centered_text("Volume"),
+                    slider(0..=127, gain, move |gain| Event::TrackGain(
+                        track.track_id,
+                        gain
+                    )),
+                ]
+                .spacing(6)
+                .align_items(Alignment::Center);
+
+                let program = ctx.output_manager.channel_program(channel);
+                let program_row = row![
+                    centered_text("Instrument"),
+                    pick_list(
+                        midi_file::INSTRUMENT_NAMES,
+                        program.map(|p| midi_file::INSTRUMENT_NAMES[p as usize]),
+                        move |name| Event::TrackProgram(track.track_id, Some(name)),
+                    )
+                    .placeholder("From File"),
+                ]
+                .spacing(6)
+                .align_items(Alignment::Center);
+
+                let body = col![body, transpose_row, destination_row, gain_row, program_row]
+                    .spacing(6);
+
                 let card = neothesia_iced_widgets::TrackCard::new()
                     .title(name)
                     .subtitle(format!("{} Notes", track.notes.len()))
@@ -219,10 +385,29 @@ impl<'a, MSG> Component<MSG, Theme, Renderer> for TracksPage<'a, MSG> {
                 .on_press(Event::AllTracksPlayer(PlayerConfig::Human))
                 .style(theme::button());
 
-            row![listen, play_along]
+            let mut row = row![listen, play_along]
                 .width(Length::Shrink)
                 .align_items(Alignment::Center)
-                .spacing(14)
+                .spacing(14);
+
+            for input in ctx.output_manager.inputs() {
+                row = row.push(
+                    button(centered_text(input.to_string()))
+                        .on_press(Event::SelectInput(input))
+                        .style(theme::button()),
+                );
+            }
+
+            if !ctx.output_manager.inputs().is_empty() {
+                let echo = ctx.output_manager.echo_input();
+                row = row.push(
+                    button(centered_text(if echo { "Echo: On" } else { "Echo: Off" }))
+                        .on_press(Event::ToggleInputEcho(!echo))
+                        .style(theme::button()),
+                );
+            }
+
+            row
         };
 
         let center = container(center)